@@ -1,4 +1,9 @@
-use redb::{ReadTransaction, WriteTransaction};
+// `redb::Error` is large, and it's threaded through every `Query`/`Statement`
+// error as the thing `?` converts into — boxing it would ripple across the
+// whole public API for no real benefit, so we accept the size here.
+#![allow(clippy::result_large_err)]
+
+use redb::{ReadTransaction, ReadableTable, ReadableTableMetadata, StorageBackend, WriteTransaction};
 
 pub trait Query<'a, T = ()> {
   type Output;
@@ -34,70 +39,101 @@ impl<'a> QueryArg<'a> for &'a ReadTransaction {
   }
 }
 
-impl<'a, F, O, E, T0> Query<'a, (T0,)> for F
-where
-  F: FnOnce(T0) -> Result<O, E>,
-  T0: QueryArg<'a>,
-  E: From<redb::Error>,
-{
-  type Output = O;
-  type Error = E;
-
-  fn run(self, tx: &'a ReadTransaction) -> Result<Self::Output, Self::Error> {
-    let t0 = T0::from_tx(tx)?;
-    self(t0)
-  }
+// `table!`'s write-side wrapper is parameterized by the transaction's
+// lifetime (`$rw<'a>`), so a bare `T` can't stand in for it in a `for<'a>`
+// bound: fixing `T` once by inference pins that lifetime, but the HRTB
+// quantifies `'a` independently, and no single `T` can equal every `'a`.
+// `TableArg` breaks that tie with a GAT: callers name the `'static`-tagged
+// wrapper, and `Bound<'a>` lets `DatabaseExt::execute`/`Session::run`
+// recover the real, lifetime-correct argument type for each `'a` in turn.
+pub trait TableArg {
+  type Bound<'a>;
 }
 
-impl<'a, F, O, E, T0, T1> Query<'a, (T0, T1)> for F
-where
-  F: FnOnce(T0, T1) -> Result<O, E>,
-  T0: QueryArg<'a>,
-  T1: QueryArg<'a>,
-  E: From<redb::Error>,
-{
-  type Output = O;
-  type Error = E;
+pub trait TableArgTuple {
+  type Bound<'a>;
+}
 
-  fn run(self, tx: &'a ReadTransaction) -> Result<Self::Output, Self::Error> {
-    let t0 = T0::from_tx(tx)?;
-    let t1 = T1::from_tx(tx)?;
-    self(t0, t1)
-  }
+// Lifts `TableArg` over tuples, for every arity `DatabaseExt`/`Session`
+// support, mirroring `impl_query!`/`impl_statement!` below.
+macro_rules! impl_table_arg_tuple {
+  ($($T:ident),+) => {
+    impl<$($T: TableArg),+> TableArgTuple for ($($T,)+) {
+      type Bound<'a> = ($($T::Bound<'a>,)+);
+    }
+  };
 }
 
-impl<'a, F, O, E, T0> Statement<'a, (T0,)> for F
-where
-  F: FnOnce(T0) -> Result<O, E>,
-  T0: StatementArg<'a>,
-  E: From<redb::Error>,
-{
-  type Output = O;
-  type Error = E;
+impl_table_arg_tuple!(T0);
+impl_table_arg_tuple!(T0, T1);
+impl_table_arg_tuple!(T0, T1, T2);
+impl_table_arg_tuple!(T0, T1, T2, T3);
+impl_table_arg_tuple!(T0, T1, T2, T3, T4);
+impl_table_arg_tuple!(T0, T1, T2, T3, T4, T5);
+impl_table_arg_tuple!(T0, T1, T2, T3, T4, T5, T6);
+impl_table_arg_tuple!(T0, T1, T2, T3, T4, T5, T6, T7);
 
-  fn execute(self, tx: &'a WriteTransaction) -> Result<Self::Output, Self::Error> {
-    let t0 = T0::from_tx(tx)?;
-    self(t0)
-  }
+// Generates a `Query` impl for an `F: FnOnce($($T),+) -> Result<O, E>` over the
+// tuple `($($T,)+)`, for every arity from one table argument up to eight.
+macro_rules! impl_query {
+  ($($T:ident),+) => {
+    impl<'a, F, O, E, $($T),+> Query<'a, ($($T,)+)> for F
+    where
+      F: FnOnce($($T),+) -> Result<O, E>,
+      $($T: QueryArg<'a>,)+
+      E: From<redb::Error>,
+    {
+      type Output = O;
+      type Error = E;
+
+      #[allow(non_snake_case)]
+      fn run(self, tx: &'a ReadTransaction) -> Result<Self::Output, Self::Error> {
+        $(let $T = $T::from_tx(tx)?;)+
+        self($($T),+)
+      }
+    }
+  };
 }
 
-impl<'a, F, O, E, T0, T1> Statement<'a, (T0, T1)> for F
-where
-  F: FnOnce(T0, T1) -> Result<O, E>,
-  T0: StatementArg<'a>,
-  T1: StatementArg<'a>,
-  E: From<redb::Error>,
-{
-  type Output = O;
-  type Error = E;
+// Likewise for `Statement`, over `WriteTransaction`/`StatementArg`.
+macro_rules! impl_statement {
+  ($($T:ident),+) => {
+    impl<'a, F, O, E, $($T),+> Statement<'a, ($($T,)+)> for F
+    where
+      F: FnOnce($($T),+) -> Result<O, E>,
+      $($T: StatementArg<'a>,)+
+      E: From<redb::Error>,
+    {
+      type Output = O;
+      type Error = E;
 
-  fn execute(self, tx: &'a WriteTransaction) -> Result<Self::Output, Self::Error> {
-    let t0 = T0::from_tx(tx)?;
-    let t1 = T1::from_tx(tx)?;
-    self(t0, t1)
-  }
+      #[allow(non_snake_case)]
+      fn execute(self, tx: &'a WriteTransaction) -> Result<Self::Output, Self::Error> {
+        $(let $T = $T::from_tx(tx)?;)+
+        self($($T),+)
+      }
+    }
+  };
 }
 
+impl_query!(T0);
+impl_query!(T0, T1);
+impl_query!(T0, T1, T2);
+impl_query!(T0, T1, T2, T3);
+impl_query!(T0, T1, T2, T3, T4);
+impl_query!(T0, T1, T2, T3, T4, T5);
+impl_query!(T0, T1, T2, T3, T4, T5, T6);
+impl_query!(T0, T1, T2, T3, T4, T5, T6, T7);
+
+impl_statement!(T0);
+impl_statement!(T0, T1);
+impl_statement!(T0, T1, T2);
+impl_statement!(T0, T1, T2, T3);
+impl_statement!(T0, T1, T2, T3, T4);
+impl_statement!(T0, T1, T2, T3, T4, T5);
+impl_statement!(T0, T1, T2, T3, T4, T5, T6);
+impl_statement!(T0, T1, T2, T3, T4, T5, T6, T7);
+
 #[macro_export]
 macro_rules! table {
   ($ro:ident, $rw:ident, $name:ident, $key:ty, $value:ty) => {
@@ -112,6 +148,11 @@ macro_rules! table {
       }
     }
 
+    impl $crate::TableArg for $rw<'static> {
+      type Bound<'a> = $rw<'a>;
+    }
+
+    #[allow(dead_code)]
     struct $ro(::redb::ReadOnlyTable<$key, $value>);
 
     impl<'a> QueryArg<'a> for $ro {
@@ -119,33 +160,495 @@ macro_rules! table {
         Ok(Self(tx.open_table($name)?))
       }
     }
+
+    impl $ro {
+      #[allow(dead_code)]
+      fn range(
+        &self,
+        range: impl ::std::ops::RangeBounds<$key>,
+      ) -> Result<$crate::RangeIter<$key, $value>, ::redb::Error> {
+        Ok($crate::RangeIter(self.0.range(range)?))
+      }
+
+      #[allow(dead_code)]
+      fn range_rev(
+        &self,
+        range: impl ::std::ops::RangeBounds<$key>,
+      ) -> Result<::std::iter::Rev<$crate::RangeIter<$key, $value>>, ::redb::Error> {
+        Ok($crate::RangeIter(self.0.range(range)?).rev())
+      }
+
+      #[allow(dead_code)]
+      fn aggregate<A, O>(
+        &self,
+        range: impl ::std::ops::RangeBounds<$key>,
+        mut aggregate: impl $crate::Aggregate<
+          ::redb::AccessGuard<'static, $key>,
+          ::redb::AccessGuard<'static, $value>,
+          A,
+          O,
+        >,
+      ) -> Result<O, ::redb::Error> {
+        let mut acc = aggregate.init();
+
+        for result in self.range(range)? {
+          let (key, value) = result?;
+          acc = aggregate.step(acc, key, value)?;
+        }
+
+        Ok(aggregate.finalize(acc))
+      }
+    }
   };
 }
 
-// This is commented out, because I can't get it to compile T_T
-#[cfg(any())]
-mod ext {
-  use {super::*, redb::Database};
+/// Iterator over `(key, value)` access guards produced by `$ro::range`/`range_rev`.
+///
+/// `AccessGuard::value` borrows from the guard itself, not from the range's
+/// own lifetime, so it can't be decoded ahead of time into the struct's
+/// `Item` the way the name might suggest: callers decode each guard with
+/// `.value()` as they consume it. This otherwise just mirrors `redb::Range`,
+/// converting its `StorageError` into the crate's `redb::Error`.
+pub struct RangeIter<K, V>(::redb::Range<'static, K, V>)
+where
+  K: ::redb::Key + 'static,
+  V: ::redb::Value + 'static;
 
-  trait DatabaseExt {
-    fn execute<'a, T, S>(&self, statement: S) -> Result<S::Output, S::Error>
-    where
-      S: Statement<'a, T>;
+impl<K, V> Iterator for RangeIter<K, V>
+where
+  K: ::redb::Key + 'static,
+  V: ::redb::Value + 'static,
+{
+  type Item = Result<(::redb::AccessGuard<'static, K>, ::redb::AccessGuard<'static, V>), redb::Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.0.next().map(|result| result.map_err(redb::Error::from))
   }
+}
 
-  impl DatabaseExt for Database {
-    fn execute<'a, T, S>(&self, statement: S) -> Result<S::Output, S::Error>
-    where
-      S: Statement<'a, T>,
-    {
-      let tx = self.begin_write().map_err(|err| redb::Error::from(err))?;
-      let result = statement.execute(&tx)?;
-      tx.commit().map_err(|err| redb::Error::from(err))?;
-      Ok(result)
+impl<K, V> DoubleEndedIterator for RangeIter<K, V>
+where
+  K: ::redb::Key + 'static,
+  V: ::redb::Value + 'static,
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self
+      .0
+      .next_back()
+      .map(|result| result.map_err(redb::Error::from))
+  }
+}
+
+/// Folds over a table range without materializing the rows, mirroring
+/// rusqlite's stateless `Aggregate` trait (init context, step per row, finalize).
+pub trait Aggregate<K, V, A, O> {
+  fn init(&mut self) -> A;
+  fn step(&mut self, acc: A, key: K, value: V) -> Result<A, redb::Error>;
+  fn finalize(&mut self, acc: A) -> O;
+}
+
+impl<K, V, A, O, Init, Step, Finalize> Aggregate<K, V, A, O> for (Init, Step, Finalize)
+where
+  Init: FnMut() -> A,
+  Step: FnMut(A, K, V) -> Result<A, redb::Error>,
+  Finalize: FnMut(A) -> O,
+{
+  fn init(&mut self) -> A {
+    (self.0)()
+  }
+
+  fn step(&mut self, acc: A, key: K, value: V) -> Result<A, redb::Error> {
+    (self.1)(acc, key, value)
+  }
+
+  fn finalize(&mut self, acc: A) -> O {
+    (self.2)(acc)
+  }
+}
+
+pub trait DatabaseExt {
+  fn execute<A, O, E, S>(&self, statement: S) -> Result<O, E>
+  where
+    A: TableArgTuple,
+    S: for<'a> Statement<'a, A::Bound<'a>, Output = O, Error = E>,
+    E: From<redb::Error>;
+
+  fn query<T, O, E, Q>(&self, query: Q) -> Result<O, E>
+  where
+    Q: for<'a> Query<'a, T, Output = O, Error = E>,
+    E: From<redb::Error>;
+
+  fn session(&self) -> Result<Session, redb::Error>;
+
+  fn read_session(&self) -> Result<ReadSession, redb::Error>;
+}
+
+impl DatabaseExt for redb::Database {
+  fn execute<A, O, E, S>(&self, statement: S) -> Result<O, E>
+  where
+    A: TableArgTuple,
+    S: for<'a> Statement<'a, A::Bound<'a>, Output = O, Error = E>,
+    E: From<redb::Error>,
+  {
+    let tx = self.begin_write().map_err(redb::Error::from)?;
+    let result = statement.execute(&tx)?;
+    tx.commit().map_err(redb::Error::from)?;
+    Ok(result)
+  }
+
+  fn query<T, O, E, Q>(&self, query: Q) -> Result<O, E>
+  where
+    Q: for<'a> Query<'a, T, Output = O, Error = E>,
+    E: From<redb::Error>,
+  {
+    let tx = self.begin_read().map_err(redb::Error::from)?;
+    query.run(&tx)
+  }
+
+  fn session(&self) -> Result<Session, redb::Error> {
+    Ok(Session::new(
+      self.begin_write().map_err(redb::Error::from)?,
+    ))
+  }
+
+  fn read_session(&self) -> Result<ReadSession, redb::Error> {
+    Ok(ReadSession::new(
+      self.begin_read().map_err(redb::Error::from)?,
+    ))
+  }
+}
+
+/// Runs many `Statement`s against a single `WriteTransaction`, committing or
+/// rolling back only once all of them have been run.
+pub struct Session {
+  tx: WriteTransaction,
+}
+
+impl Session {
+  fn new(tx: WriteTransaction) -> Self {
+    Self { tx }
+  }
+
+  pub fn run<A, O, E, S>(&self, statement: S) -> Result<O, E>
+  where
+    A: TableArgTuple,
+    S: for<'a> Statement<'a, A::Bound<'a>, Output = O, Error = E>,
+    E: From<redb::Error>,
+  {
+    statement.execute(&self.tx)
+  }
+
+  pub fn commit(self) -> Result<(), redb::Error> {
+    self.tx.commit().map_err(redb::Error::from)
+  }
+
+  pub fn rollback(self) -> Result<(), redb::Error> {
+    self.tx.abort().map_err(redb::Error::from)
+  }
+}
+
+/// Runs many `Query`s against a single `ReadTransaction`.
+pub struct ReadSession {
+  tx: ReadTransaction,
+}
+
+impl ReadSession {
+  fn new(tx: ReadTransaction) -> Self {
+    Self { tx }
+  }
+
+  pub fn run<T, O, E, Q>(&self, query: Q) -> Result<O, E>
+  where
+    Q: for<'a> Query<'a, T, Output = O, Error = E>,
+    E: From<redb::Error>,
+  {
+    query.run(&self.tx)
+  }
+}
+
+// Bookkeeping tables the `Versioned` ring owns directly, opened with
+// `tx.open_table` rather than through `table!`'s `$ro`/`$rw` wrappers:
+// nothing here ever runs as a `Statement`/`Query` argument, so the extra
+// wrapper types and their `range`/`aggregate` methods would just be dead
+// code.
+const SAVEPOINTS: redb::TableDefinition<u64, u64> = redb::TableDefinition::new("savepoints");
+
+const VERSION_COUNTER: redb::TableDefinition<u64, u64> =
+  redb::TableDefinition::new("version_counter");
+
+const VERSION_COUNTER_KEY: u64 = 0;
+
+#[derive(Debug)]
+pub enum VersionedError {
+  Redb(redb::Error),
+  Savepoint(redb::SavepointError),
+  Io(std::io::Error),
+  /// The requested version is not in the savepoint ring: it has been
+  /// evicted, or no savepoint was ever recorded under it.
+  VersionNotRetained(u64),
+  /// `execute`'s statement committed successfully, but recording the
+  /// savepoint for its resulting version failed afterward, at any step of
+  /// that second transaction: the write already took effect, and is NOT rolled back, but
+  /// the savepoint ring no longer has an entry for it. Inspect the boxed
+  /// error to see what failed.
+  StatementCommittedSnapshotFailed(Box<VersionedError>),
+}
+
+impl From<redb::Error> for VersionedError {
+  fn from(err: redb::Error) -> Self {
+    Self::Redb(err)
+  }
+}
+
+impl From<redb::SavepointError> for VersionedError {
+  fn from(err: redb::SavepointError) -> Self {
+    Self::Savepoint(err)
+  }
+}
+
+impl From<std::io::Error> for VersionedError {
+  fn from(err: std::io::Error) -> Self {
+    Self::Io(err)
+  }
+}
+
+impl std::fmt::Display for VersionedError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Redb(err) => write!(f, "{err}"),
+      Self::Savepoint(err) => write!(f, "{err}"),
+      Self::Io(err) => write!(f, "{err}"),
+      Self::VersionNotRetained(version) => {
+        write!(f, "version {version} is no longer retained")
+      }
+      Self::StatementCommittedSnapshotFailed(err) => {
+        write!(
+          f,
+          "statement committed, but recording its snapshot failed: {err}"
+        )
+      }
     }
   }
 }
 
+impl std::error::Error for VersionedError {}
+
+/// A `Database` that keeps a bounded ring of persistent savepoints, one per
+/// committed `Statement`, so past versions can be read with `query_as_of`.
+///
+/// Mirrors toydb's `begin_as_of(version)`: each version is a point-in-time
+/// snapshot, and only the most recent `retention` versions are kept.
+pub struct Versioned {
+  database: redb::Database,
+  path: std::path::PathBuf,
+  retention: u64,
+  // `execute` runs in two transactions that must land together from every
+  // other `execute` call's perspective (see `execute`'s doc comment below);
+  // this serializes calls so they can never interleave across threads.
+  execute_lock: std::sync::Mutex<()>,
+}
+
+impl Versioned {
+  pub fn create(
+    path: impl AsRef<std::path::Path>,
+    retention: u64,
+  ) -> Result<Self, redb::Error> {
+    let path = path.as_ref().to_path_buf();
+
+    Ok(Self {
+      database: redb::Database::create(&path)?,
+      path,
+      retention,
+      execute_lock: std::sync::Mutex::new(()),
+    })
+  }
+
+  /// Runs `statement` to completion and commits it, then takes a savepoint
+  /// of the resulting state under the next version number in a second,
+  /// immediately-following transaction, evicting the oldest retained
+  /// version if the ring is now over capacity.
+  ///
+  /// This needs two transactions rather than one: `persistent_savepoint`
+  /// only succeeds on a transaction that hasn't opened any tables yet, so
+  /// it can't be taken after `statement` has already opened its tables in
+  /// the same transaction. `execute_lock` makes the pair atomic with
+  /// respect to every other call to this method, so two overlapping
+  /// callers can never interleave between the statement's commit and the
+  /// savepoint's: without that, one call's savepoint could end up
+  /// recording the other's write under the wrong version, corrupting the
+  /// ring for both.
+  ///
+  /// If the statement's transaction fails to commit, nothing happened and
+  /// this returns a plain `VersionedError`. But once that commit succeeds,
+  /// the write is durable even if the bookkeeping transaction that follows
+  /// it fails for any reason (including a crash): in that case this
+  /// returns `VersionedError::StatementCommittedSnapshotFailed` instead,
+  /// so callers can tell "your write took effect, but it has no matching
+  /// snapshot in the ring" apart from "nothing happened".
+  pub fn execute<A, O, E, S>(&self, statement: S) -> Result<O, VersionedError>
+  where
+    A: TableArgTuple,
+    S: for<'a> Statement<'a, A::Bound<'a>, Output = O, Error = E>,
+    E: Into<VersionedError>,
+  {
+    let _guard = self.execute_lock.lock().unwrap();
+
+    let tx = self
+      .database
+      .begin_write()
+      .map_err(redb::Error::from)?;
+
+    let result = statement.execute(&tx).map_err(Into::into)?;
+
+    tx.commit().map_err(redb::Error::from)?;
+
+    self
+      .record_snapshot()
+      .map_err(|err| VersionedError::StatementCommittedSnapshotFailed(Box::new(err)))?;
+
+    Ok(result)
+  }
+
+  /// Takes a persistent savepoint of the current database state under the
+  /// next version number, evicting the oldest retained version if the ring
+  /// is now over capacity. Called immediately after `execute`'s statement
+  /// has committed; split out so its errors can be wrapped separately from
+  /// the statement's.
+  fn record_snapshot(&self) -> Result<(), VersionedError> {
+    let savepoint_tx = self
+      .database
+      .begin_write()
+      .map_err(redb::Error::from)?;
+
+    let savepoint_id = savepoint_tx.persistent_savepoint()?;
+
+    let version = {
+      let mut counter = savepoint_tx
+        .open_table(VERSION_COUNTER)
+        .map_err(redb::Error::from)?;
+
+      let version = counter
+        .get(VERSION_COUNTER_KEY)
+        .map_err(redb::Error::from)?
+        .map(|guard| guard.value())
+        .unwrap_or(0);
+
+      counter
+        .insert(VERSION_COUNTER_KEY, version + 1)
+        .map_err(redb::Error::from)?;
+
+      version
+    };
+
+    let evicted = {
+      let mut savepoints = savepoint_tx
+        .open_table(SAVEPOINTS)
+        .map_err(redb::Error::from)?;
+
+      savepoints
+        .insert(version, savepoint_id)
+        .map_err(redb::Error::from)?;
+
+      if savepoints.len().map_err(redb::Error::from)? > self.retention {
+        let oldest: Option<(u64, u64)> = savepoints
+          .range::<u64>(..)
+          .map_err(redb::Error::from)?
+          .next()
+          .transpose()
+          .map_err(redb::Error::from)?
+          .map(|(key, value)| (key.value(), value.value()));
+
+        if let Some((oldest_version, oldest_savepoint)) = oldest {
+          savepoints
+            .remove(oldest_version)
+            .map_err(redb::Error::from)?;
+
+          Some(oldest_savepoint)
+        } else {
+          None
+        }
+      } else {
+        None
+      }
+    };
+
+    if let Some(oldest_savepoint) = evicted {
+      savepoint_tx.delete_persistent_savepoint(oldest_savepoint)?;
+    }
+
+    savepoint_tx.commit().map_err(redb::Error::from)?;
+
+    Ok(())
+  }
+
+  /// Runs `query` against the database state as of `version`, then discards
+  /// every change it made: the live database is never mutated.
+  ///
+  /// This takes a `Statement`, not a `Query`, because restoring a
+  /// persistent savepoint is only possible on a `WriteTransaction` (`redb`
+  /// has no equivalent on `ReadTransaction`), so there's no way to hand the
+  /// caller anything but the mutable table wrapper here.
+  ///
+  /// `restore_savepoint` permanently invalidates every persistent savepoint
+  /// newer than the one it restores to, in the database's in-process
+  /// tracker, regardless of whether the transaction that called it is later
+  /// aborted — so restoring on the live database would silently foreclose
+  /// ever reading a later version again. To honor the "never mutated"
+  /// promise, the restore instead happens on an ephemeral in-memory clone
+  /// of the database, which is discarded once `query` has run.
+  ///
+  /// That clone still costs one `std::fs::read` of the whole database
+  /// file up front, so this scales with database size, not with the size
+  /// of `query`'s own working set — it's the right trade for occasional
+  /// time-travel reads, but isn't free, and callers doing this often
+  /// against a large database should expect the read to dominate.
+  pub fn query_as_of<A, O, E, Q>(&self, version: u64, query: Q) -> Result<O, VersionedError>
+  where
+    A: TableArgTuple,
+    Q: for<'a> Statement<'a, A::Bound<'a>, Output = O, Error = E>,
+    E: Into<VersionedError>,
+  {
+    let read_tx = self.database.begin_read().map_err(redb::Error::from)?;
+
+    let savepoint_id = {
+      let savepoints = read_tx
+        .open_table(SAVEPOINTS)
+        .map_err(redb::Error::from)?;
+
+      let savepoint_id = savepoints
+        .get(version)
+        .map_err(redb::Error::from)?
+        .map(|guard| guard.value());
+
+      savepoint_id.ok_or(VersionedError::VersionNotRetained(version))?
+    };
+
+    let bytes = std::fs::read(&self.path)?;
+
+    let backend = redb::backends::InMemoryBackend::new();
+    backend.set_len(bytes.len() as u64)?;
+    backend.write(0, &bytes)?;
+
+    let scratch_database = redb::Database::builder()
+      .create_with_backend(backend)
+      .map_err(redb::Error::from)?;
+
+    let mut tx = scratch_database
+      .begin_write()
+      .map_err(redb::Error::from)?;
+
+    let savepoint = tx.get_persistent_savepoint(savepoint_id)?;
+
+    tx.restore_savepoint(&savepoint)?;
+
+    let result = query.execute(&tx).map_err(Into::into);
+
+    tx.abort().map_err(redb::Error::from)?;
+
+    result
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use {super::*, redb::Database, tempfile::TempDir};
@@ -235,4 +738,330 @@ mod tests {
 
     assert_eq!(result, Some("smith".into()));
   }
+
+  #[test]
+  fn database_ext_execute_and_query() {
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    fn insert(mut names: NamesMut) -> Result<(), redb::Error> {
+      names.0.insert("james", "smith")?;
+      Ok(())
+    }
+
+    database
+      .execute::<(NamesMut<'static>,), _, _, _>(insert)
+      .unwrap();
+
+    fn get(names: Names) -> Result<Option<String>, redb::Error> {
+      Ok(names.0.get("james")?.map(|guard| guard.value().into()))
+    }
+
+    let result = database.query(get).unwrap();
+
+    assert_eq!(result, Some("smith".into()));
+  }
+
+  #[test]
+  fn statement_with_three_table_args() {
+    table! {
+      Accounts, AccountsMut, ACCOUNTS, &'static str, i64
+    }
+
+    table! {
+      Log, LogMut, LOG, u64, &'static str
+    }
+
+    table! {
+      Meta, MetaMut, META, &'static str, u64
+    }
+
+    fn initialize(
+      mut accounts: AccountsMut,
+      mut log: LogMut,
+      mut meta: MetaMut,
+    ) -> Result<(), redb::Error> {
+      accounts.0.insert("alice", 100)?;
+      accounts.0.insert("bob", 0)?;
+      log.0.insert(0, "open accounts")?;
+      meta.0.insert("transfers", 0)?;
+      Ok(())
+    }
+
+    fn transfer(
+      mut accounts: AccountsMut,
+      mut log: LogMut,
+      mut meta: MetaMut,
+    ) -> Result<(), redb::Error> {
+      let alice = accounts.0.get("alice")?.unwrap().value();
+      let bob = accounts.0.get("bob")?.unwrap().value();
+
+      accounts.0.insert("alice", alice - 25)?;
+      accounts.0.insert("bob", bob + 25)?;
+
+      log.0.insert(1, "alice -> bob: 25")?;
+
+      let transfers = meta.0.get("transfers")?.unwrap().value();
+      meta.0.insert("transfers", transfers + 1)?;
+
+      Ok(())
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    type ThreeTables = (AccountsMut<'static>, LogMut<'static>, MetaMut<'static>);
+
+    database.execute::<ThreeTables, _, _, _>(initialize).unwrap();
+    database.execute::<ThreeTables, _, _, _>(transfer).unwrap();
+
+    fn balances(accounts: Accounts) -> Result<(i64, i64), redb::Error> {
+      Ok((
+        accounts.0.get("alice")?.unwrap().value(),
+        accounts.0.get("bob")?.unwrap().value(),
+      ))
+    }
+
+    let result = database.query(balances).unwrap();
+
+    assert_eq!(result, (75, 25));
+  }
+
+  #[test]
+  fn session_runs_many_statements_in_one_transaction() {
+    table! {
+      Users, UsersMut, USERS, &'static str, &'static str
+    }
+
+    fn insert_james(mut users: UsersMut) -> Result<(), redb::Error> {
+      users.0.insert("james", "smith")?;
+      Ok(())
+    }
+
+    fn insert_jane(mut users: UsersMut) -> Result<(), redb::Error> {
+      users.0.insert("jane", "doe")?;
+      Ok(())
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    let session = database.session().unwrap();
+
+    session
+      .run::<(UsersMut<'static>,), _, _, _>(insert_james)
+      .unwrap();
+    session
+      .run::<(UsersMut<'static>,), _, _, _>(insert_jane)
+      .unwrap();
+
+    session.commit().unwrap();
+
+    fn both(users: Users) -> Result<(Option<String>, Option<String>), redb::Error> {
+      Ok((
+        users.0.get("james")?.map(|guard| guard.value().into()),
+        users.0.get("jane")?.map(|guard| guard.value().into()),
+      ))
+    }
+
+    let read_session = database.read_session().unwrap();
+
+    let result = read_session.run(both).unwrap();
+
+    assert_eq!(result, (Some("smith".into()), Some("doe".into())));
+  }
+
+  #[test]
+  fn session_rollback_discards_statements() {
+    table! {
+      Pending, PendingMut, PENDING, &'static str, &'static str
+    }
+
+    fn insert(key: &'static str) -> impl FnOnce(PendingMut) -> Result<(), redb::Error> {
+      move |mut pending: PendingMut| {
+        pending.0.insert(key, "value")?;
+        Ok(())
+      }
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    database
+      .execute::<(PendingMut<'static>,), _, _, _>(insert("committed"))
+      .unwrap();
+
+    let session = database.session().unwrap();
+
+    session
+      .run::<(PendingMut<'static>,), _, _, _>(insert("rolled-back"))
+      .unwrap();
+
+    session.rollback().unwrap();
+
+    fn get(key: &'static str) -> impl FnOnce(Pending) -> Result<Option<String>, redb::Error> {
+      move |pending: Pending| Ok(pending.0.get(key)?.map(|guard| guard.value().into()))
+    }
+
+    assert_eq!(
+      database.query(get("committed")).unwrap(),
+      Some("value".into())
+    );
+    assert_eq!(database.query(get("rolled-back")).unwrap(), None);
+  }
+
+  #[test]
+  fn range_scans_over_ordered_keys() {
+    table! {
+      Scores, ScoresMut, SCORES, &'static str, i64
+    }
+
+    fn initialize(mut scores: ScoresMut) -> Result<(), redb::Error> {
+      scores.0.insert("alice", 1)?;
+      scores.0.insert("bob", 2)?;
+      scores.0.insert("carol", 3)?;
+      scores.0.insert("dave", 4)?;
+      Ok(())
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    database
+      .execute::<(ScoresMut<'static>,), _, _, _>(initialize)
+      .unwrap();
+
+    // `AccessGuard::value` borrows from the guard, which doesn't outlive a
+    // single `map` step, so collecting a range means decoding into owned
+    // data rather than the borrowed `&'static str` the table itself stores.
+    fn forward(scores: Scores) -> Result<Vec<(String, i64)>, redb::Error> {
+      scores
+        .range("bob".."dave")?
+        .map(|result| result.map(|(key, value)| (key.value().to_string(), value.value())))
+        .collect()
+    }
+
+    let result = database.query(forward).unwrap();
+
+    assert_eq!(
+      result,
+      vec![("bob".to_string(), 2), ("carol".to_string(), 3)]
+    );
+
+    fn reverse(scores: Scores) -> Result<Vec<(String, i64)>, redb::Error> {
+      scores
+        .range_rev(..)?
+        .map(|result| result.map(|(key, value)| (key.value().to_string(), value.value())))
+        .collect()
+    }
+
+    let result = database.query(reverse).unwrap();
+
+    assert_eq!(
+      result,
+      vec![
+        ("dave".to_string(), 4),
+        ("carol".to_string(), 3),
+        ("bob".to_string(), 2),
+        ("alice".to_string(), 1),
+      ]
+    );
+  }
+
+  #[test]
+  fn aggregate_sums_a_range_without_collecting() {
+    table! {
+      Weights, WeightsMut, WEIGHTS, &'static str, i64
+    }
+
+    fn initialize(mut weights: WeightsMut) -> Result<(), redb::Error> {
+      weights.0.insert("a", 1)?;
+      weights.0.insert("b", 2)?;
+      weights.0.insert("c", 3)?;
+      Ok(())
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let database = Database::create(dir.path().join("database.redb")).unwrap();
+
+    database
+      .execute::<(WeightsMut<'static>,), _, _, _>(initialize)
+      .unwrap();
+
+    fn sum(weights: Weights) -> Result<i64, redb::Error> {
+      weights.aggregate(
+        ..,
+        (
+          || 0i64,
+          |acc, _key, value: redb::AccessGuard<i64>| Ok(acc + value.value()),
+          |acc| acc,
+        ),
+      )
+    }
+
+    assert_eq!(database.query(sum).unwrap(), 6);
+
+    fn sum_of_empty_range(weights: Weights) -> Result<i64, redb::Error> {
+      weights.aggregate(
+        "z".."zz",
+        (
+          || 0i64,
+          |acc, _key, value: redb::AccessGuard<i64>| Ok(acc + value.value()),
+          |acc| acc,
+        ),
+      )
+    }
+
+    assert_eq!(database.query(sum_of_empty_range).unwrap(), 0);
+  }
+
+  #[test]
+  fn versioned_reads_keep_only_the_retained_window() {
+    table! {
+      Counter, CounterMut, COUNTER, &'static str, i64
+    }
+
+    fn set(value: i64) -> impl FnOnce(CounterMut) -> Result<(), redb::Error> {
+      move |mut counter: CounterMut| {
+        counter.0.insert("n", value)?;
+        Ok(())
+      }
+    }
+
+    fn get(counter: CounterMut) -> Result<i64, redb::Error> {
+      Ok(counter.0.get("n")?.unwrap().value())
+    }
+
+    let dir = TempDir::new().unwrap();
+
+    let versioned = Versioned::create(dir.path().join("versioned.redb"), 2).unwrap();
+
+    type CounterArg = (CounterMut<'static>,);
+
+    versioned.execute::<CounterArg, _, _, _>(set(0)).unwrap();
+    versioned.execute::<CounterArg, _, _, _>(set(1)).unwrap();
+    versioned.execute::<CounterArg, _, _, _>(set(2)).unwrap();
+
+    assert_eq!(
+      versioned.query_as_of::<CounterArg, _, _, _>(1, get).unwrap(),
+      1
+    );
+    assert_eq!(
+      versioned.query_as_of::<CounterArg, _, _, _>(2, get).unwrap(),
+      2
+    );
+
+    assert!(matches!(
+      versioned
+        .query_as_of::<CounterArg, _, _, _>(0, get)
+        .unwrap_err(),
+      VersionedError::VersionNotRetained(0)
+    ));
+  }
 }